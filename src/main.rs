@@ -1,93 +1,418 @@
-use std::marker::PhantomData;
-
+use halo2_gadgets::poseidon::{
+    primitives::{ConstantLength, P128Pow5T3},
+    Hash as PoseidonHash, Pow5Chip, Pow5Config,
+};
 use halo2wrong::halo2::{
     arithmetic::FieldExt,
     circuit::{AssignedCell, Layouter, Region, SimpleFloorPlanner, Value},
-    plonk::{Advice, Circuit, Column, ConstraintSystem, Error, Expression, Instance, Selector},
+    plonk::{
+        Advice, Circuit, Column, ConstraintSystem, Error, Expression, Fixed, Instance, Selector,
+        TableColumn,
+    },
     poly::Rotation,
 };
 
-trait Params<F: FieldExt> {
-    fn layer1_weights() -> [[F; 128]; 10];
-    fn layer2_weights() -> [[F; 10]; 128];
+/// Width and rate of the Poseidon sponge used to commit to the network's
+/// input, following the `P128Pow5T3` spec used throughout the halo2
+/// ecosystem (e.g. orchard).
+const POSEIDON_WIDTH: usize = 3;
+const POSEIDON_RATE: usize = 2;
+
+/// Number of input nodes committed to by the Poseidon hash. `ConstantLength`
+/// needs this fixed at compile time, unlike the rest of the network's
+/// now-configurable layer sizes, so a model's declared input layer must be
+/// exactly this wide; `configure_with_params` asserts this eagerly instead
+/// of letting a mismatched `Params` panic later inside `synthesize`.
+const POSEIDON_INPUT_LEN: usize = 10;
+
+/// Upper bound on `Quantization::scale_bits + value_bits`. Both are used as
+/// `1u64 << bits` to size a lookup table, which overflows a `u64` shift as
+/// the sum approaches 64 and, long before that, produces a table far bigger
+/// than any practical `MockProver`/real prover `k` could hold — 2^20 rows is
+/// already a lot. `configure_with_params` asserts against this eagerly
+/// instead of panicking (or silently wrapping, in release builds) deep
+/// inside table construction.
+const MAX_QUANTIZATION_BITS: usize = 20;
+
+/// Per-layer dimensions and trained weight matrices for a `NeuralNetwork`
+/// circuit instance. `weights[l][j][i]` is the weight connecting node `i` of
+/// layer `l` to node `j` of layer `l + 1`, so `weights.len() ==
+/// layer_sizes.len() - 1`.
+#[derive(Clone, Default)]
+struct Params<F: FieldExt> {
+    layer_sizes: Vec<usize>,
+    weights: Vec<Vec<Vec<F>>>,
+    quantization: Quantization,
+}
+
+/// Fixed-point rescaling configuration. A matmul's accumulator doubles the
+/// quantization scale of its inputs, so every layer transition divides it
+/// back down by `2^scale_bits` before the result is carried forward.
+/// `value_bits` bounds the quotient that results, and is reused to size the
+/// magnitude table for the sign/magnitude decomposition the (rescaled) value
+/// subsequently goes through in the ReLU gate.
+#[derive(Clone, Copy, Default)]
+struct Quantization {
+    scale_bits: usize,
+    value_bits: usize,
 }
 
-struct NeuralNetwork<F: FieldExt, P: Params<F>> {
-    layer1_values: [Value<F>; 10],
-    layer2_values: [Value<F>; 128],
-    layer3_values: [Value<F>; 10],
-    _params: PhantomData<P>,
+struct NeuralNetwork<F: FieldExt> {
+    /// Witnessed node values for every layer, including the input layer.
+    layer_values: Vec<Vec<Value<F>>>,
+    params: Params<F>,
 }
 
 #[derive(Clone)]
-struct NeuralNetworkConfig {
-    node_columns: [Column<Advice>; 128],
-    layer_selectors: [Selector; 3],
+struct NeuralNetworkConfig<F: FieldExt> {
+    node_columns: Vec<Column<Advice>>,
+    layer_selectors: Vec<Selector>,
     output_column: Column<Instance>,
+    // ReLU activation columns and selector. `relu_input`/`relu_output` hold
+    // one pre-/post-activation value per row, so a single `sign`/`magnitude`
+    // pair can range-check every node across as many rows as the widest
+    // layer needs.
+    relu_input: Column<Advice>,
+    relu_output: Column<Advice>,
+    sign_column: Column<Advice>,
+    magnitude_column: Column<Advice>,
+    magnitude_table: TableColumn,
+    relu_selector: Selector,
+    layer_sizes: Vec<usize>,
+    // `weight_columns[i]` carries the weight from input node `i` to
+    // whichever output node the current row computes, for whichever layer
+    // transition is assigned into its row — the same columns (and thus the
+    // same verifying key) serve every layer, every output node, and every
+    // trained model. This is a row-major layout: each output node gets its
+    // own row pair (inputs, then weights+output) rather than every node
+    // pair getting its own fixed column, so the column count is linear in
+    // `max_width` instead of quadratic.
+    weight_columns: Vec<Column<Fixed>>,
+    // Holds a single output node's raw (pre-rescale) accumulator, one row
+    // per node, mirroring `relu_input`/`rescale_input`'s one-column/many-row
+    // layout.
+    accumulator_column: Column<Advice>,
+    // Poseidon commitment over the input layer, exposed as a public input
+    // so a verifier knows the proof was generated for a committed input
+    // rather than an arbitrary witness.
+    poseidon_config: Pow5Config<F, POSEIDON_WIDTH, POSEIDON_RATE>,
+    commitment_column: Column<Instance>,
+    // Fixed-point rescaling: `rescale_input` carries a layer's raw
+    // accumulator, `quotient_column`/`remainder_column` witness the
+    // division by `2^scale_bits`, and `remainder_table` range-checks the
+    // remainder into `[0, 2^scale_bits)`.
+    rescale_input: Column<Advice>,
+    quotient_column: Column<Advice>,
+    remainder_column: Column<Advice>,
+    remainder_table: TableColumn,
+    rescale_selector: Selector,
+    // Bounds a rescaled quotient's magnitude via the same `sign`/`magnitude`
+    // decomposition and lookup the ReLU gate uses for hidden layers. The
+    // final layer has no ReLU to do this for it, so without a `bound` step
+    // its quotient would only be tied to `remainder` by the rescale gate,
+    // which a prover could satisfy with an arbitrary quotient.
+    bound_selector: Selector,
+    quantization: Quantization,
+}
+
+/// Splits a signed fixed-point value into its `sign` bit and unsigned
+/// `magnitude`, so that `value = (1 - 2*sign) * magnitude`. `value_bits`
+/// bounds the magnitude, matching `Quantization::value_bits`.
+fn decompose_signed<F: FieldExt>(value: Value<F>, value_bits: usize) -> (Value<F>, Value<F>) {
+    let threshold = F::from(1u64 << value_bits);
+    value
+        .map(|v| {
+            if v >= threshold {
+                // Values in the upper half of the range represent negatives
+                // wrapped around the field modulus.
+                (F::one(), F::zero() - v)
+            } else {
+                (F::zero(), v)
+            }
+        })
+        .unzip()
+}
+
+/// Splits a fixed-point accumulator into a `quotient` and `remainder` such
+/// that `value = quotient * 2^scale_bits + remainder`, with `remainder` kept
+/// in `[0, 2^scale_bits)` so it can be range-checked by a lookup. `value` may
+/// be negative under the same sign/magnitude wraparound convention as
+/// `decompose_signed` (the matmul accumulator this divides is a signed sum of
+/// signed products, so it is almost never a small positive field element);
+/// `accumulator_bits` bounds its magnitude the way `value_bits` bounds
+/// `decompose_signed`'s. Floor division is used so `remainder` stays
+/// non-negative even when `value` is negative.
+fn divmod_pow2<F: FieldExt>(
+    value: Value<F>,
+    scale_bits: usize,
+    accumulator_bits: usize,
+) -> (Value<F>, Value<F>) {
+    let scale = 1u64 << scale_bits;
+    let (sign, magnitude) = decompose_signed(value, accumulator_bits);
+    sign.zip(magnitude)
+        .map(|(sign, magnitude)| {
+            let repr = magnitude.to_repr();
+            let bytes = repr.as_ref();
+            let mut raw = 0u64;
+            for (i, &byte) in bytes.iter().take(8).enumerate() {
+                raw |= (byte as u64) << (8 * i);
+            }
+
+            if sign == F::zero() {
+                (F::from(raw / scale), F::from(raw % scale))
+            } else if raw % scale == 0 {
+                (F::zero() - F::from(raw / scale), F::zero())
+            } else {
+                // `-magnitude` doesn't divide evenly: round the quotient
+                // down (i.e. further from zero) and let the remainder make
+                // up the difference, so it stays in `[0, 2^scale_bits)`
+                // instead of going negative.
+                (
+                    F::zero() - F::from(raw / scale + 1),
+                    F::from(scale) - F::from(raw % scale),
+                )
+            }
+        })
+        .unzip()
 }
 
-impl<F: FieldExt, P: Params<F>> Circuit<F> for NeuralNetwork<F, P> {
-    type Config = NeuralNetworkConfig;
+impl<F: FieldExt> Circuit<F> for NeuralNetwork<F> {
+    type Config = NeuralNetworkConfig<F>;
     type FloorPlanner = SimpleFloorPlanner;
+    type Params = Params<F>;
 
     fn without_witnesses(&self) -> Self {
         Self {
-            layer1_values: [Value::unknown(); 10],
-            layer2_values: [Value::unknown(); 128],
-            layer3_values: [Value::unknown(); 10],
-            _params: PhantomData,
+            layer_values: self
+                .params
+                .layer_sizes
+                .iter()
+                .map(|&size| vec![Value::unknown(); size])
+                .collect(),
+            params: self.params.clone(),
         }
     }
 
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
     fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
-        let node_columns = [(); 128].map(|_| meta.advice_column());
-        let layer_selectors = [(); 3].map(|_| meta.selector());
+        // The `Circuit` trait requires this method, but a `NeuralNetwork`
+        // only makes sense with real layer sizes and weights, which arrive
+        // through `configure_with_params`.
+        Self::configure_with_params(meta, Params::default())
+    }
+
+    fn configure_with_params(meta: &mut ConstraintSystem<F>, params: Self::Params) -> Self::Config {
+        // `ConstantLength` fixes the Poseidon commitment's input width at
+        // compile time, so an input layer of any other size can never be
+        // synthesized; catch that here rather than deep inside
+        // `synthesize`. `configure()`'s placeholder `Params::default()` has
+        // an empty `layer_sizes` and must not trip this.
+        if let Some(&input_size) = params.layer_sizes.first() {
+            assert_eq!(
+                input_size, POSEIDON_INPUT_LEN,
+                "NeuralNetwork's input layer must have exactly {} nodes to match POSEIDON_INPUT_LEN",
+                POSEIDON_INPUT_LEN
+            );
+        }
+
+        let total_quantization_bits =
+            params.quantization.scale_bits + params.quantization.value_bits;
+        assert!(
+            total_quantization_bits <= MAX_QUANTIZATION_BITS,
+            "Quantization::scale_bits + value_bits must be at most {} (got {} + {} = {}): \
+             each is used to size a lookup table via `1 << bits`, which overflows near 64 \
+             bits and is impractically large well before that",
+            MAX_QUANTIZATION_BITS,
+            params.quantization.scale_bits,
+            params.quantization.value_bits,
+            total_quantization_bits
+        );
+
+        let max_width = params.layer_sizes.iter().copied().max().unwrap_or(0);
+        let node_columns: Vec<Column<Advice>> = (0..max_width).map(|_| meta.advice_column()).collect();
+        // One selector per layer transition (not per layer): it gates the
+        // matmul gate tying a layer's assigned nodes to the next layer's.
+        let layer_selectors: Vec<Selector> = (0..params.layer_sizes.len().saturating_sub(1))
+            .map(|_| meta.selector())
+            .collect();
         let output_column = meta.instance_column();
+        let commitment_column = meta.instance_column();
 
-        // Constrain Layer 2
-        meta.create_gate("layer_2", |v_cells| {
-            let node_exps: [Expression<F>; 10] = (0..10)
-                .map(|i| v_cells.query_advice(node_columns[i], Rotation::cur()))
-                .collect::<Vec<Expression<F>>>()
-                .try_into()
-                .unwrap();
-            let l1_weights = P::layer1_weights();
-            let mut next_values = [(); 128].map(|_| Expression::Constant(F::zero()));
-
-            for i in 0..10 {
-                for j in 0..128 {
-                    let next_v = node_exps[i].clone() * l1_weights[j][i];
-                    next_values[j] = next_values[j].clone() + next_v;
-                }
-            }
+        let poseidon_state = [(); POSEIDON_WIDTH].map(|_| meta.advice_column());
+        let poseidon_partial_sbox = meta.advice_column();
+        let poseidon_rc_a = [(); POSEIDON_WIDTH].map(|_| meta.fixed_column());
+        let poseidon_rc_b = [(); POSEIDON_WIDTH].map(|_| meta.fixed_column());
+        for column in poseidon_state {
+            meta.enable_equality(column);
+        }
+        // The Poseidon sponge loads its initial state from constants, which
+        // needs a fixed column enabled for global constants.
+        meta.enable_constant(poseidon_rc_b[0]);
+        let poseidon_config = Pow5Chip::configure::<P128Pow5T3<F>>(
+            meta,
+            poseidon_state,
+            poseidon_partial_sbox,
+            poseidon_rc_a,
+            poseidon_rc_b,
+        );
+
+        let relu_input = meta.advice_column();
+        let relu_output = meta.advice_column();
+        let sign_column = meta.advice_column();
+        let magnitude_column = meta.advice_column();
+        let magnitude_table = meta.lookup_table_column();
+        let relu_selector = meta.selector();
+
+        let accumulator_column = meta.advice_column();
+        for &column in &node_columns {
+            meta.enable_equality(column);
+        }
+        meta.enable_equality(relu_input);
+        meta.enable_equality(relu_output);
+        meta.enable_equality(accumulator_column);
 
-            next_values.to_vec()
+        // Weight matrices live in fixed columns rather than gate constants,
+        // so the constraint system (and therefore the verifying key) is the
+        // same for every trained model; only the fixed assignments in
+        // `synthesize` change from model to model.
+        //
+        // Row-major layout: `weight_columns[i]` holds the weight from input
+        // node `i` to whichever output node the current row pair computes,
+        // so one output node's matmul is spread over its own row pair
+        // (inputs, then weights + accumulator) instead of every node pair
+        // getting its own fixed column. This keeps the column count linear
+        // in `max_width` rather than quadratic, at the cost of `out_size`
+        // row pairs per layer transition instead of one.
+        let weight_columns: Vec<Column<Fixed>> =
+            (0..max_width).map(|_| meta.fixed_column()).collect();
+
+        // One matmul gate per layer transition, enabled once per output
+        // node at that node's row pair. It reaches across to the next row
+        // with `Rotation::next()` and constrains the accumulator already
+        // assigned there to equal the weighted sum of the current row's
+        // (copied-in) input nodes — without this, the sum is never compared
+        // against anything and the layer's output is unconstrained.
+        for layer in 0..params.layer_sizes.len().saturating_sub(1) {
+            let in_size = params.layer_sizes[layer];
+            let weight_columns = weight_columns.clone();
+            let selector = layer_selectors[layer];
+
+            meta.create_gate(format!("layer_{}", layer), move |v_cells| {
+                let s = v_cells.query_selector(selector);
+                let assigned_next = v_cells.query_advice(accumulator_column, Rotation::next());
+                let sum = (0..in_size).fold(Expression::Constant(F::zero()), |acc, i| {
+                    let node = v_cells.query_advice(node_columns[i], Rotation::cur());
+                    let weight = v_cells.query_fixed(weight_columns[i], Rotation::next());
+                    acc + node * weight
+                });
+
+                vec![s * (assigned_next - sum)]
+            });
+        }
+
+        // Range-check the ReLU magnitude against the table of valid magnitudes.
+        meta.lookup("relu magnitude range check", |v_cells| {
+            let magnitude = v_cells.query_advice(magnitude_column, Rotation::cur());
+            vec![(magnitude, magnitude_table)]
         });
 
-        // Constrain Output Layer
-        meta.create_gate("out_layer", |v_cells| {
-            let node_exps: [Expression<F>; 128] = (0..128)
-                .map(|i| v_cells.query_advice(node_columns[i], Rotation::cur()))
-                .collect::<Vec<Expression<F>>>()
-                .try_into()
-                .unwrap();
-            let l2_weights = P::layer2_weights();
-            let mut next_values = [(); 10].map(|_| Expression::Constant(F::zero()));
-
-            for i in 0..128 {
-                for j in 0..10 {
-                    let next_v = node_exps[i].clone() * l2_weights[j][i];
-                    next_values[j] = next_values[j].clone() + next_v;
-                }
-            }
+        // Constrain the ReLU activation applied to a hidden layer's
+        // pre-activations. `sign`/`magnitude` decompose `relu_input` and
+        // `relu_output` is `relu_input` gated to zero whenever `sign` is set.
+        // `sign` must also be proven boolean: without that, a prover could
+        // pick a non-0/1 `sign` alongside any `magnitude` from the lookup
+        // table and land `output` on an arbitrary value instead of the true
+        // ReLU result.
+        meta.create_gate("relu", |v_cells| {
+            let s = v_cells.query_selector(relu_selector);
+            let sign = v_cells.query_advice(sign_column, Rotation::cur());
+            let magnitude = v_cells.query_advice(magnitude_column, Rotation::cur());
+            let input = v_cells.query_advice(relu_input, Rotation::cur());
+            let output = v_cells.query_advice(relu_output, Rotation::cur());
+
+            let one = Expression::Constant(F::one());
+            let two = Expression::Constant(F::from(2));
+
+            let boolean = sign.clone() * (one.clone() - sign.clone());
+            let decompose = input.clone() - (one.clone() - two * sign.clone()) * magnitude;
+            let activate = output - (one - sign) * input;
+
+            vec![s.clone() * boolean, s.clone() * decompose, s * activate]
+        });
+
+        // Bound the final layer's output the same way `relu` bounds hidden
+        // layers, but without the activation term: there is no ReLU after
+        // the last transition, so nothing else ties its quotient's
+        // magnitude down.
+        let bound_selector = meta.selector();
+        meta.create_gate("bound", |v_cells| {
+            let s = v_cells.query_selector(bound_selector);
+            let sign = v_cells.query_advice(sign_column, Rotation::cur());
+            let magnitude = v_cells.query_advice(magnitude_column, Rotation::cur());
+            let input = v_cells.query_advice(relu_input, Rotation::cur());
+
+            let one = Expression::Constant(F::one());
+            let two = Expression::Constant(F::from(2));
+
+            let boolean = sign.clone() * (one.clone() - sign.clone());
+            let decompose = input - (one - two * sign) * magnitude;
 
-            next_values.to_vec()
+            vec![s.clone() * boolean, s * decompose]
+        });
+
+        let rescale_input = meta.advice_column();
+        let quotient_column = meta.advice_column();
+        let remainder_column = meta.advice_column();
+        let remainder_table = meta.lookup_table_column();
+        let rescale_selector = meta.selector();
+        meta.enable_equality(rescale_input);
+        meta.enable_equality(quotient_column);
+
+        // Range-check the rescale remainder against the table of valid
+        // remainders.
+        meta.lookup("rescale remainder range check", |v_cells| {
+            let remainder = v_cells.query_advice(remainder_column, Rotation::cur());
+            vec![(remainder, remainder_table)]
+        });
+
+        // Constrain the fixed-point rescale: `accumulator = quotient *
+        // 2^scale_bits + remainder`. `remainder`'s range check (above) is
+        // what proves the division is exact rather than an arbitrary split.
+        let scale = F::from(1u64 << params.quantization.scale_bits);
+        meta.create_gate("rescale", |v_cells| {
+            let s = v_cells.query_selector(rescale_selector);
+            let accumulator = v_cells.query_advice(rescale_input, Rotation::cur());
+            let quotient = v_cells.query_advice(quotient_column, Rotation::cur());
+            let remainder = v_cells.query_advice(remainder_column, Rotation::cur());
+
+            vec![s * (accumulator - (quotient * scale + remainder))]
         });
 
         NeuralNetworkConfig {
             node_columns,
             output_column,
             layer_selectors,
+            relu_input,
+            relu_output,
+            sign_column,
+            magnitude_column,
+            magnitude_table,
+            relu_selector,
+            layer_sizes: params.layer_sizes,
+            weight_columns,
+            accumulator_column,
+            poseidon_config,
+            commitment_column,
+            rescale_input,
+            quotient_column,
+            remainder_column,
+            remainder_table,
+            rescale_selector,
+            bound_selector,
+            quantization: params.quantization,
         }
     }
 
@@ -96,70 +421,278 @@ impl<F: FieldExt, P: Params<F>> Circuit<F> for NeuralNetwork<F, P> {
         config: Self::Config,
         mut layouter: impl Layouter<F>,
     ) -> Result<(), Error> {
-        // Assigne values in Layer 1
-        layouter.assign_region(
-            || "layer_1",
-            |mut region: Region<'_, F>| {
-                // Enable gates for Layer 1
-                config.layer_selectors[0].enable(&mut region, 0)?;
-
-                for i in 0..self.layer1_values.len() {
-                    region.assign_advice(
-                        || format!("layer1_node_{}", i),
-                        config.node_columns[i],
-                        0,
-                        || self.layer1_values[i],
+        layouter.assign_table(
+            || "relu magnitude table",
+            |mut table| {
+                for magnitude in 0..(1u64 << config.quantization.value_bits) {
+                    table.assign_cell(
+                        || "magnitude",
+                        config.magnitude_table,
+                        magnitude as usize,
+                        || Value::known(F::from(magnitude)),
                     )?;
                 }
-
                 Ok(())
             },
         )?;
 
-        // Assign values in Layer 2
-        layouter.assign_region(
-            || "layer_2",
-            |mut region: Region<'_, F>| {
-                // Enable gates for Layer 2
-                config.layer_selectors[1].enable(&mut region, 0)?;
-
-                for i in 0..self.layer2_values.len() {
-                    region.assign_advice(
-                        || format!("layer2_node_{}", i),
-                        config.node_columns[i],
-                        0,
-                        || self.layer2_values[i],
+        layouter.assign_table(
+            || "rescale remainder table",
+            |mut table| {
+                for remainder in 0..(1u64 << config.quantization.scale_bits) {
+                    table.assign_cell(
+                        || "remainder",
+                        config.remainder_table,
+                        remainder as usize,
+                        || Value::known(F::from(remainder)),
                     )?;
                 }
-
                 Ok(())
             },
         )?;
 
-        // Assign values in Layer 3
-        let output_layer3 = layouter.assign_region(
-            || "layer_2",
-            |mut region: Region<'_, F>| {
-                // Enable gates for Layer 3
-                config.layer_selectors[2].enable(&mut region, 0)?;
+        let num_layers = config.layer_sizes.len();
 
-                let mut output: [Option<AssignedCell<F, F>>; 10] = [(); 10].map(|_| None);
-                for i in 0..self.layer3_values.len() {
+        // The input layer has no incoming matmul, so it is assigned once on
+        // its own; every later layer is assigned as the "next row" of the
+        // transition that produces it.
+        let mut current_cells: Vec<AssignedCell<F, F>> = layouter.assign_region(
+            || "layer_0",
+            |mut region: Region<'_, F>| {
+                let mut cells = Vec::with_capacity(self.layer_values[0].len());
+                for i in 0..self.layer_values[0].len() {
                     let cell = region.assign_advice(
-                        || format!("layer3_node_{}", i),
+                        || format!("layer0_node_{}", i),
                         config.node_columns[i],
                         0,
-                        || self.layer3_values[i],
+                        || self.layer_values[0][i],
                     )?;
-                    output[i] = Some(cell);
+                    cells.push(cell);
                 }
-
-                Ok(output.map(|x| x.unwrap()))
+                Ok(cells)
             },
         )?;
 
-        for i in 0..output_layer3.len() {
-            layouter.constrain_instance(output_layer3[i].cell(), config.output_column, i)?;
+        // Commit to the input layer with Poseidon and expose the digest as
+        // a public input, so a verifier can check the proof was generated
+        // for a committed input rather than an arbitrary witness.
+        let input_message: [AssignedCell<F, F>; POSEIDON_INPUT_LEN] = current_cells
+            .clone()
+            .try_into()
+            .expect("input layer must have exactly POSEIDON_INPUT_LEN nodes");
+        let poseidon_chip = Pow5Chip::construct(config.poseidon_config.clone());
+        let commitment = PoseidonHash::<
+            _,
+            _,
+            P128Pow5T3<F>,
+            ConstantLength<POSEIDON_INPUT_LEN>,
+            POSEIDON_WIDTH,
+            POSEIDON_RATE,
+        >::init(poseidon_chip, layouter.namespace(|| "poseidon init"))?
+        .hash(layouter.namespace(|| "poseidon hash input"), input_message)?;
+        layouter.constrain_instance(commitment.cell(), config.commitment_column, 0)?;
+
+        for layer in 0..num_layers.saturating_sub(1) {
+            let out_size = self.layer_values[layer + 1].len();
+            let is_output_layer = layer == num_layers - 2;
+
+            // Each output node gets its own row pair: row `2*j` copies this
+            // layer's (already-activated, if hidden) cells back in so the
+            // gate can reach them with `Rotation::cur()`, and row `2*j + 1`
+            // assigns output node `j`'s weight row and raw accumulator so
+            // `Rotation::next()` can reach those.
+            let next_cells = layouter.assign_region(
+                || format!("layer_{}_matmul", layer),
+                |mut region: Region<'_, F>| {
+                    let mut cells = Vec::with_capacity(out_size);
+                    for j in 0..out_size {
+                        let input_row = 2 * j;
+                        let output_row = input_row + 1;
+                        config.layer_selectors[layer].enable(&mut region, input_row)?;
+
+                        for (i, cell) in current_cells.iter().enumerate() {
+                            let copied = region.assign_advice(
+                                || format!("layer{}_node_{}_for_output_{}", layer, i, j),
+                                config.node_columns[i],
+                                input_row,
+                                || cell.value().copied(),
+                            )?;
+                            region.constrain_equal(copied.cell(), cell.cell())?;
+                        }
+
+                        // `weights[j][i]` is the weight from input node `i`
+                        // to output node `j`.
+                        for (i, &weight) in self.params.weights[layer][j].iter().enumerate() {
+                            region.assign_fixed(
+                                || format!("weight_{}_{}_{}", layer, i, j),
+                                config.weight_columns[i],
+                                output_row,
+                                || Value::known(weight),
+                            )?;
+                        }
+
+                        let cell = region.assign_advice(
+                            || format!("layer{}_node_{}", layer + 1, j),
+                            config.accumulator_column,
+                            output_row,
+                            || self.layer_values[layer + 1][j],
+                        )?;
+                        cells.push(cell);
+                    }
+
+                    Ok(cells)
+                },
+            )?;
+
+            // Rescale the raw accumulator back down to the network's
+            // quantization scale before it is carried forward, one row per
+            // node, so a single quotient/remainder/lookup pair range-checks
+            // every node regardless of layer width.
+            let rescaled_cells = layouter.assign_region(
+                || format!("rescale_{}", layer + 1),
+                |mut region: Region<'_, F>| {
+                    let mut cells: Vec<AssignedCell<F, F>> = Vec::with_capacity(next_cells.len());
+                    for (i, accumulator) in next_cells.iter().enumerate() {
+                        config.rescale_selector.enable(&mut region, i)?;
+
+                        let input_cell = region.assign_advice(
+                            || format!("rescale_input_{}", i),
+                            config.rescale_input,
+                            i,
+                            || accumulator.value().copied(),
+                        )?;
+                        region.constrain_equal(input_cell.cell(), accumulator.cell())?;
+
+                        let (quotient, remainder) = divmod_pow2(
+                            accumulator.value().copied(),
+                            config.quantization.scale_bits,
+                            config.quantization.value_bits + config.quantization.scale_bits,
+                        );
+                        region.assign_advice(
+                            || format!("remainder_{}", i),
+                            config.remainder_column,
+                            i,
+                            || remainder,
+                        )?;
+                        let quotient_cell = region.assign_advice(
+                            || format!("quotient_{}", i),
+                            config.quotient_column,
+                            i,
+                            || quotient,
+                        )?;
+                        cells.push(quotient_cell);
+                    }
+
+                    Ok(cells)
+                },
+            )?;
+
+            current_cells = if is_output_layer {
+                // Bound the final output's magnitude the same way a hidden
+                // layer's activation is bounded, so the quotient above can't
+                // be an arbitrary value chosen to satisfy the rescale gate
+                // via `remainder`.
+                layouter.assign_region(
+                    || format!("bound_{}", layer + 1),
+                    |mut region: Region<'_, F>| {
+                        let mut cells: Vec<AssignedCell<F, F>> =
+                            Vec::with_capacity(rescaled_cells.len());
+                        for (i, value) in rescaled_cells.iter().enumerate() {
+                            config.bound_selector.enable(&mut region, i)?;
+
+                            let input_cell = region.assign_advice(
+                                || format!("bound_input_{}", i),
+                                config.relu_input,
+                                i,
+                                || value.value().copied(),
+                            )?;
+                            region.constrain_equal(input_cell.cell(), value.cell())?;
+
+                            let (sign, magnitude) = decompose_signed(
+                                value.value().copied(),
+                                config.quantization.value_bits,
+                            );
+                            region.assign_advice(
+                                || format!("sign_{}", i),
+                                config.sign_column,
+                                i,
+                                || sign,
+                            )?;
+                            region.assign_advice(
+                                || format!("magnitude_{}", i),
+                                config.magnitude_column,
+                                i,
+                                || magnitude,
+                            )?;
+
+                            cells.push(input_cell);
+                        }
+
+                        Ok(cells)
+                    },
+                )?
+            } else {
+                // Apply the ReLU activation to every hidden layer: one row
+                // per node, so a single sign/magnitude/lookup pair
+                // range-checks every node regardless of layer width.
+                layouter.assign_region(
+                    || format!("relu_{}", layer + 1),
+                    |mut region: Region<'_, F>| {
+                        let mut cells: Vec<AssignedCell<F, F>> =
+                            Vec::with_capacity(rescaled_cells.len());
+                        for (i, pre_activation) in rescaled_cells.iter().enumerate() {
+                            config.relu_selector.enable(&mut region, i)?;
+
+                            let input_cell = region.assign_advice(
+                                || format!("relu_input_{}", i),
+                                config.relu_input,
+                                i,
+                                || pre_activation.value().copied(),
+                            )?;
+                            region.constrain_equal(input_cell.cell(), pre_activation.cell())?;
+
+                            let (sign, magnitude) = decompose_signed(
+                                pre_activation.value().copied(),
+                                config.quantization.value_bits,
+                            );
+                            region.assign_advice(
+                                || format!("sign_{}", i),
+                                config.sign_column,
+                                i,
+                                || sign,
+                            )?;
+                            region.assign_advice(
+                                || format!("magnitude_{}", i),
+                                config.magnitude_column,
+                                i,
+                                || magnitude,
+                            )?;
+
+                            let activated =
+                                pre_activation.value().copied().zip(sign).map(|(v, s)| {
+                                    if s == F::one() {
+                                        F::zero()
+                                    } else {
+                                        v
+                                    }
+                                });
+                            let output_cell = region.assign_advice(
+                                || format!("relu_output_{}", i),
+                                config.relu_output,
+                                i,
+                                || activated,
+                            )?;
+                            cells.push(output_cell);
+                        }
+
+                        Ok(cells)
+                    },
+                )?
+            };
+        }
+
+        for (i, cell) in current_cells.iter().enumerate() {
+            layouter.constrain_instance(cell.cell(), config.output_column, i)?;
         }
         Ok(())
     }
@@ -168,3 +701,179 @@ impl<F: FieldExt, P: Params<F>> Circuit<F> for NeuralNetwork<F, P> {
 fn main() {
     println!("Hello, world!");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_gadgets::poseidon::primitives::Hash as PoseidonNativeHash;
+    use halo2wrong::curves::bn256::Fr;
+    use halo2wrong::halo2::dev::MockProver;
+
+    #[test]
+    fn poseidon_commitment_matches_native_hash() {
+        let layer_sizes = vec![POSEIDON_INPUT_LEN, 4, 2];
+        let weights = vec![
+            vec![vec![Fr::zero(); POSEIDON_INPUT_LEN]; 4],
+            vec![vec![Fr::zero(); 4]; 2],
+        ];
+        let params = Params {
+            layer_sizes,
+            weights,
+            // Small enough that the magnitude/remainder tables fit within
+            // `MockProver::run`'s `k = 10` (1024 rows) below.
+            quantization: Quantization {
+                scale_bits: 4,
+                value_bits: 8,
+            },
+        };
+
+        let input = [Fr::from(7); POSEIDON_INPUT_LEN];
+        let expected_digest = PoseidonNativeHash::<
+            Fr,
+            P128Pow5T3<Fr>,
+            ConstantLength<POSEIDON_INPUT_LEN>,
+            POSEIDON_WIDTH,
+            POSEIDON_RATE,
+        >::init()
+        .hash(input);
+
+        let layer_values = vec![
+            input.iter().map(|&v| Value::known(v)).collect(),
+            vec![Value::known(Fr::zero()); 4],
+            vec![Value::known(Fr::zero()); 2],
+        ];
+        let circuit = NeuralNetwork {
+            layer_values,
+            params,
+        };
+
+        let output_instance = vec![Fr::zero(); 2];
+        let commitment_instance = vec![expected_digest];
+
+        let prover = MockProver::run(10, &circuit, vec![output_instance, commitment_instance]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+    }
+
+    #[test]
+    fn rescale_handles_negative_accumulator() {
+        // A single output-layer transition with weight -1 everywhere turns
+        // 10 ones into an accumulator of -10, exercising the sign-wraparound
+        // path of `divmod_pow2` that the all-zero-weight test above never
+        // reaches.
+        let layer_sizes = vec![POSEIDON_INPUT_LEN, 1];
+        let weights = vec![vec![vec![Fr::zero() - Fr::one(); POSEIDON_INPUT_LEN]; 1]];
+        let params = Params {
+            layer_sizes,
+            weights,
+            quantization: Quantization {
+                scale_bits: 4,
+                value_bits: 8,
+            },
+        };
+
+        let input = [Fr::one(); POSEIDON_INPUT_LEN];
+        let expected_digest = PoseidonNativeHash::<
+            Fr,
+            P128Pow5T3<Fr>,
+            ConstantLength<POSEIDON_INPUT_LEN>,
+            POSEIDON_WIDTH,
+            POSEIDON_RATE,
+        >::init()
+        .hash(input);
+
+        let accumulator = Fr::zero() - Fr::from(10);
+        let layer_values = vec![
+            input.iter().map(|&v| Value::known(v)).collect(),
+            vec![Value::known(accumulator)],
+        ];
+        let circuit = NeuralNetwork {
+            layer_values,
+            params,
+        };
+
+        // -10 = quotient * 16 + remainder with remainder in [0, 16):
+        // quotient = -1, remainder = 6.
+        let output_instance = vec![Fr::zero() - Fr::one()];
+        let commitment_instance = vec![expected_digest];
+
+        let prover = MockProver::run(10, &circuit, vec![output_instance, commitment_instance]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+    }
+
+    // All-ones weights over a [10, 3, 2] network: each hidden node sums the
+    // 10 inputs (accumulator 10), each output node sums the 3 hidden
+    // activations (accumulator 30). `scale_bits: 0` makes rescale an
+    // identity, so these are exactly the values the matmul gate must tie
+    // `layer_values` to across both transitions.
+    fn multilayer_matmul_params() -> Params<Fr> {
+        Params {
+            layer_sizes: vec![POSEIDON_INPUT_LEN, 3, 2],
+            weights: vec![
+                vec![vec![Fr::one(); POSEIDON_INPUT_LEN]; 3],
+                vec![vec![Fr::one(); 3]; 2],
+            ],
+            quantization: Quantization {
+                scale_bits: 0,
+                value_bits: 8,
+            },
+        }
+    }
+
+    fn multilayer_matmul_digest(input: [Fr; POSEIDON_INPUT_LEN]) -> Fr {
+        PoseidonNativeHash::<
+            Fr,
+            P128Pow5T3<Fr>,
+            ConstantLength<POSEIDON_INPUT_LEN>,
+            POSEIDON_WIDTH,
+            POSEIDON_RATE,
+        >::init()
+        .hash(input)
+    }
+
+    #[test]
+    fn multilayer_matmul_accepts_correct_witness() {
+        let input = [Fr::one(); POSEIDON_INPUT_LEN];
+        let layer_values = vec![
+            input.iter().map(|&v| Value::known(v)).collect(),
+            vec![Value::known(Fr::from(10)); 3],
+            vec![Value::known(Fr::from(30)); 2],
+        ];
+        let circuit = NeuralNetwork {
+            layer_values,
+            params: multilayer_matmul_params(),
+        };
+
+        let output_instance = vec![Fr::from(30); 2];
+        let commitment_instance = vec![multilayer_matmul_digest(input)];
+
+        let prover = MockProver::run(10, &circuit, vec![output_instance, commitment_instance]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+    }
+
+    #[test]
+    fn multilayer_matmul_rejects_tampered_witness() {
+        let input = [Fr::one(); POSEIDON_INPUT_LEN];
+        let layer_values = vec![
+            input.iter().map(|&v| Value::known(v)).collect(),
+            // The true hidden-layer accumulator is 10; claim 11 instead. The
+            // matmul gate should catch this even though the other hidden
+            // nodes and the output layer are untouched.
+            vec![
+                Value::known(Fr::from(11)),
+                Value::known(Fr::from(10)),
+                Value::known(Fr::from(10)),
+            ],
+            vec![Value::known(Fr::from(30)); 2],
+        ];
+        let circuit = NeuralNetwork {
+            layer_values,
+            params: multilayer_matmul_params(),
+        };
+
+        let output_instance = vec![Fr::from(30); 2];
+        let commitment_instance = vec![multilayer_matmul_digest(input)];
+
+        let prover = MockProver::run(10, &circuit, vec![output_instance, commitment_instance]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+}